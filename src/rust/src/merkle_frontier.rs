@@ -1,7 +1,7 @@
 use core::mem::size_of_val;
 use core::pin::Pin;
 
-use incrementalmerkletree::{bridgetree, Altitude, Frontier, Hashable};
+use incrementalmerkletree::{bridgetree, Altitude, Frontier, Hashable, Position, Tree};
 use orchard::{bundle::Authorized, tree::MerkleHashOrchard};
 use tracing::error;
 use zcash_primitives::{
@@ -9,6 +9,7 @@ use zcash_primitives::{
         incremental::{read_frontier_v1, write_frontier_v1},
         CommitmentTree, HashSer,
     },
+    sapling,
     transaction::components::Amount,
 };
 
@@ -25,6 +26,53 @@ mod ffi {
         type RustStream = crate::streams::ffi::RustStream;
     }
 
+    /// The kind of failure that stopped an `append_commitments` call early, if any.
+    ///
+    /// This is the machine-readable counterpart to [`AppendResult::error`]: callers should
+    /// branch on `error_kind` rather than matching substrings of the human-readable
+    /// `error` string.
+    #[namespace = "merkle_frontier"]
+    enum AppendErrorKind {
+        /// All commitments were appended successfully.
+        None = 0,
+        /// A commitment could not be parsed.
+        InvalidEncoding = 1,
+        /// The tree has no room for any more commitments.
+        TreeFull = 2,
+    }
+
+    /// The outcome of a bulk append via `append_commitments`.
+    ///
+    /// Unlike a bare `bool`, this always reports how many of the given commitments were
+    /// successfully appended before `error_kind` (if any) was encountered, so that
+    /// callers appending many blocks' worth of commitments in one call (e.g. during
+    /// initial block download) can recover the exact failure point instead of rewinding
+    /// to the start.
+    #[namespace = "merkle_frontier"]
+    struct AppendResult {
+        /// The number of commitments appended before `error_kind` (if any) was
+        /// encountered.
+        appended: u64,
+        /// The size of the frontier after the append.
+        size: u64,
+        /// The root of the frontier after the append.
+        root: [u8; 32],
+        /// `None` on success; otherwise the structured reason appending stopped early.
+        error_kind: AppendErrorKind,
+        /// Empty on success; otherwise a human-readable description of `error_kind`.
+        error: String,
+    }
+
+    /// An authentication path for a single note commitment, as returned by
+    /// `authentication_path`.
+    #[namespace = "merkle_frontier"]
+    struct AuthPath {
+        /// The position of the note commitment this path authenticates.
+        position: u64,
+        /// The 32 sibling hashes needed to reconstruct the Merkle path for a spend.
+        siblings: [[u8; 32]; 32],
+    }
+
     #[namespace = "merkle_frontier"]
     extern "Rust" {
         type Orchard;
@@ -32,16 +80,53 @@ mod ffi {
         type OrchardWallet;
 
         fn orchard_empty_root() -> [u8; 32];
+        fn orchard_empty_roots() -> [[u8; 32]; 33];
         fn new_orchard() -> Box<Orchard>;
         fn box_clone(self: &Orchard) -> Box<Orchard>;
         fn parse_orchard(stream: Pin<&mut RustStream>) -> Result<Box<Orchard>>;
+        fn parse_orchard_legacy(stream: Pin<&mut RustStream>) -> Result<Box<Orchard>>;
         fn serialize(self: &Orchard, stream: Pin<&mut RustStream>) -> Result<()>;
         fn serialize_legacy(self: &Orchard, stream: Pin<&mut RustStream>) -> Result<()>;
         fn dynamic_memory_usage(self: &Orchard) -> usize;
         fn root(self: &Orchard) -> [u8; 32];
         fn size(self: &Orchard) -> u64;
+        fn recalculate_root(self: &Orchard) -> [u8; 32];
+        fn verify_root(self: &Orchard, expected: [u8; 32]) -> bool;
         unsafe fn append_bundle(self: &mut Orchard, bundle: *const OrchardBundle) -> bool;
+        fn append_commitments(self: &mut Orchard, cmxs: &[[u8; 32]]) -> AppendResult;
         unsafe fn init_wallet(self: &Orchard, wallet: *mut OrchardWallet) -> bool;
+
+        type Sapling;
+        type SaplingBundle;
+
+        fn new_sapling() -> Box<Sapling>;
+        fn box_clone(self: &Sapling) -> Box<Sapling>;
+        fn parse_sapling(stream: Pin<&mut RustStream>) -> Result<Box<Sapling>>;
+        fn serialize(self: &Sapling, stream: Pin<&mut RustStream>) -> Result<()>;
+        fn serialize_legacy(self: &Sapling, stream: Pin<&mut RustStream>) -> Result<()>;
+        fn dynamic_memory_usage(self: &Sapling) -> usize;
+        fn root(self: &Sapling) -> [u8; 32];
+        fn size(self: &Sapling) -> u64;
+        fn recalculate_root(self: &Sapling) -> [u8; 32];
+        fn verify_root(self: &Sapling, expected: [u8; 32]) -> bool;
+        unsafe fn append_bundle(self: &mut Sapling, bundle: *const SaplingBundle) -> bool;
+
+        type OrchardWitnessTree;
+
+        fn new_orchard_witness_tree() -> Box<OrchardWitnessTree>;
+        fn box_clone(self: &OrchardWitnessTree) -> Box<OrchardWitnessTree>;
+        fn root(self: &OrchardWitnessTree) -> [u8; 32];
+        fn size(self: &OrchardWitnessTree) -> u64;
+        unsafe fn append_bundle(self: &mut OrchardWitnessTree, bundle: *const OrchardBundle) -> bool;
+        fn append_commitment(self: &mut OrchardWitnessTree, cmx: [u8; 32]) -> Result<u64>;
+        fn checkpoint(self: &mut OrchardWitnessTree);
+        fn rewind(self: &mut OrchardWitnessTree) -> bool;
+        fn witness(self: &mut OrchardWitnessTree) -> Result<u64>;
+        fn authentication_path(
+            self: &OrchardWitnessTree,
+            position: u64,
+            root: [u8; 32],
+        ) -> Result<AuthPath>;
     }
 }
 
@@ -67,6 +152,21 @@ impl<H: Copy + Hashable + HashSer> MerkleFrontier<H> {
         }
     }
 
+    /// Attempts to parse a Merkle frontier from the given C++ stream, using the legacy
+    /// `CommitmentTree` encoding that was in use prior to the v5 transaction format.
+    ///
+    /// This is the inverse of [`CommitmentTree::from_frontier`], and exists so that node
+    /// databases and wallets that still store trees in the pre-upgrade encoding can be
+    /// loaded without requiring a full rescan.
+    fn parse_legacy(stream: Pin<&mut ffi::RustStream>) -> Result<Box<Self>, String> {
+        let reader = CppStream::from(stream);
+
+        match CommitmentTree::read(reader) {
+            Ok(parsed) => Ok(Box::new(MerkleFrontier(parsed.to_frontier()))),
+            Err(e) => Err(format!("Failed to parse legacy Merkle frontier: {}", e)),
+        }
+    }
+
     /// Serializes the frontier to the given C++ stream.
     fn serialize(&self, stream: Pin<&mut ffi::RustStream>) -> Result<(), String> {
         let writer = CppStream::from(stream);
@@ -108,6 +208,73 @@ impl<H: Copy + Hashable + HashSer> MerkleFrontier<H> {
     fn size(&self) -> u64 {
         self.0.position().map_or(0, |p| <u64>::from(p) + 1)
     }
+
+    /// Recomputes the root of this frontier from scratch, independent of [`Self::root`],
+    /// by converting to the legacy `CommitmentTree` representation (a separate,
+    /// non-incremental implementation that rebuilds the root from the raw ommers rather
+    /// than reusing `bridgetree`'s own root computation) and computing its root.
+    ///
+    /// This gives callers a cheap way to assert frontier integrity after deserialization
+    /// and at power-of-two boundaries without trusting the stored root bytes.
+    fn recalculate_root(&self) -> [u8; 32] {
+        let mut root = [0; 32];
+        CommitmentTree::from_frontier(&self.0)
+            .root()
+            .write(&mut root[..])
+            .expect("root is 32 bytes");
+        root
+    }
+
+    /// Returns whether `expected` matches this frontier's recalculated root.
+    fn verify_root(&self, expected: [u8; 32]) -> bool {
+        self.recalculate_root() == expected
+    }
+
+    /// Appends a contiguous slice of raw note commitments to this frontier, stopping at
+    /// the first one that cannot be parsed or appended.
+    ///
+    /// Unlike [`MerkleFrontier::append_bundle`], the returned [`ffi::AppendResult`]
+    /// reports how many commitments were appended and the frontier's resulting size and
+    /// root even on failure, and distinguishes a full tree from a malformed commitment.
+    fn append_commitments(&mut self, cmxs: &[[u8; 32]]) -> ffi::AppendResult {
+        for (appended, cmx) in cmxs.iter().enumerate() {
+            let node = match H::read(&cmx[..]) {
+                Ok(node) => node,
+                Err(e) => {
+                    return ffi::AppendResult {
+                        appended: appended as u64,
+                        size: self.size(),
+                        root: self.root(),
+                        error_kind: ffi::AppendErrorKind::InvalidEncoding,
+                        error: format!("Invalid commitment encoding: {}", e),
+                    }
+                }
+            };
+
+            if !self.0.append(&node) {
+                error!(
+                    "Merkle tree is full after appending {} of {} commitments.",
+                    appended,
+                    cmxs.len(),
+                );
+                return ffi::AppendResult {
+                    appended: appended as u64,
+                    size: self.size(),
+                    root: self.root(),
+                    error_kind: ffi::AppendErrorKind::TreeFull,
+                    error: "Merkle tree is full".to_string(),
+                };
+            }
+        }
+
+        ffi::AppendResult {
+            appended: cmxs.len() as u64,
+            size: self.size(),
+            root: self.root(),
+            error_kind: ffi::AppendErrorKind::None,
+            error: String::new(),
+        }
+    }
 }
 
 /// Returns the root of an empty Orchard Merkle tree.
@@ -116,6 +283,19 @@ fn orchard_empty_root() -> [u8; 32] {
     MerkleHashOrchard::empty_root(altitude).to_bytes()
 }
 
+/// Returns the canonical root of an empty Orchard Merkle (sub)tree at every altitude from
+/// the leaf (`0`) up to `MERKLE_DEPTH`, inclusive.
+///
+/// This lets consensus code assert frontier integrity at power-of-two tree sizes, where
+/// the frontier's right edge must hash against these canonical empty-subtree roots.
+fn orchard_empty_roots() -> [[u8; 32]; MERKLE_DEPTH as usize + 1] {
+    let mut roots = [[0u8; 32]; MERKLE_DEPTH as usize + 1];
+    for (altitude, root) in roots.iter_mut().enumerate() {
+        *root = MerkleHashOrchard::empty_root(Altitude::from(altitude as u8)).to_bytes();
+    }
+    roots
+}
+
 /// An Orchard incremental Merkle frontier.
 type Orchard = MerkleFrontier<MerkleHashOrchard>;
 
@@ -129,6 +309,12 @@ fn parse_orchard(stream: Pin<&mut ffi::RustStream>) -> Result<Box<Orchard>, Stri
     Orchard::parse(stream)
 }
 
+/// Attempts to parse an Orchard Merkle frontier from the given C++ stream, using the
+/// legacy `CommitmentTree` encoding.
+fn parse_orchard_legacy(stream: Pin<&mut ffi::RustStream>) -> Result<Box<Orchard>, String> {
+    Orchard::parse_legacy(stream)
+}
+
 struct OrchardBundle;
 struct OrchardWallet;
 
@@ -158,4 +344,180 @@ impl Orchard {
     fn init_wallet(&self, wallet: *mut OrchardWallet) -> bool {
         crate::wallet::orchard_wallet_init_from_frontier(wallet as *mut Wallet, &self.0)
     }
-}
\ No newline at end of file
+}
+
+/// A Sapling incremental Merkle frontier.
+type Sapling = MerkleFrontier<sapling::Node>;
+
+/// Constructs a new empty Sapling Merkle frontier.
+fn new_sapling() -> Box<Sapling> {
+    Box::new(MerkleFrontier(Inner::empty()))
+}
+
+/// Attempts to parse a Sapling Merkle frontier from the given C++ stream.
+fn parse_sapling(stream: Pin<&mut ffi::RustStream>) -> Result<Box<Sapling>, String> {
+    Sapling::parse(stream)
+}
+
+struct SaplingBundle;
+
+impl Sapling {
+    /// Appends the note commitments in the given bundle to this frontier.
+    ///
+    /// Unlike [`Orchard`], there is no `init_wallet`: the Sapling note commitment tree in
+    /// the wallet is still driven from the C++ side, not from a Rust `OrchardWallet`-style
+    /// handle.
+    fn append_bundle(&mut self, bundle: *const SaplingBundle) -> bool {
+        let bundle = unsafe { (bundle as *const sapling::Bundle<sapling::Authorized>).as_ref() };
+
+        if let Some(bundle) = bundle {
+            for output in bundle.shielded_outputs() {
+                if !self.0.append(&sapling::Node::from_cmu(&output.cmu)) {
+                    error!("Sapling note commitment tree is full.");
+                    return false;
+                }
+            }
+        }
+
+        true
+    }
+}
+
+type WitnessTreeInner<H> = bridgetree::BridgeTree<H, MERKLE_DEPTH>;
+
+/// An incremental Merkle tree that, unlike [`MerkleFrontier`], retains enough internal
+/// state to produce authentication paths (witnesses) for previously-appended note
+/// commitments, and to checkpoint and rewind that state across reorgs.
+///
+/// This lets the wallet obtain spend witnesses directly from Rust instead of maintaining
+/// its own incremental witnesses in C++.
+#[derive(Clone)]
+struct WitnessTree<H>(WitnessTreeInner<H>);
+
+impl<H: Copy + Hashable + HashSer + Ord> WitnessTree<H> {
+    /// Returns a copy of the value.
+    fn box_clone(&self) -> Box<Self> {
+        Box::new(self.clone())
+    }
+
+    /// Returns the number of leaves appended to the tree.
+    fn size(&self) -> u64 {
+        self.0.current_position().map_or(0, |p| <u64>::from(p) + 1)
+    }
+
+    /// Obtains the current root of this tree by hashing against empty nodes up to the
+    /// maximum height of the tree.
+    fn root(&self) -> [u8; 32] {
+        let mut root = [0; 32];
+        self.0
+            .root(0)
+            .expect("checkpoint depth 0 is always available")
+            .write(&mut root[..])
+            .expect("root is 32 bytes");
+        root
+    }
+
+    /// Creates a new checkpoint for the current tree state, so that it can later be
+    /// restored via [`WitnessTree::rewind`] if a reorg occurs.
+    fn checkpoint(&mut self) {
+        self.0.checkpoint();
+    }
+
+    /// Rewinds the tree state to the previous checkpoint, discarding everything appended
+    /// since then. Returns `false` if there is no checkpoint to rewind to.
+    fn rewind(&mut self) -> bool {
+        self.0.rewind()
+    }
+
+    /// Appends a single note commitment to this tree, returning its position.
+    ///
+    /// Unlike [`WitnessTree::append_bundle`], which appends a whole bundle's worth of
+    /// commitments in one uninterruptible pass, this lets a caller append commitments one
+    /// at a time and call [`WitnessTree::witness`] for the specific leaf it needs (e.g. a
+    /// wallet's own note) before appending anything else.
+    fn append_commitment(&mut self, cmx: [u8; 32]) -> Result<u64, String> {
+        let node =
+            H::read(&cmx[..]).map_err(|e| format!("Invalid commitment encoding: {}", e))?;
+
+        if !self.0.append(&node) {
+            return Err("Merkle tree is full".to_string());
+        }
+
+        self.0
+            .current_position()
+            .map(u64::from)
+            .ok_or_else(|| "Tree has no position after append".to_string())
+    }
+
+    /// Marks the most-recently-appended note commitment for later witnessing, so that an
+    /// authentication path can be produced for it even after further commitments have
+    /// been appended.
+    ///
+    /// `bridgetree` can only mark the current leaf as it is appended, not an arbitrary
+    /// past position, so callers that need witnesses for earlier leaves must call this
+    /// immediately after appending the note commitment of interest and record the
+    /// returned position themselves.
+    fn witness(&mut self) -> Result<u64, String> {
+        self.0
+            .witness()
+            .map(u64::from)
+            .ok_or_else(|| "No leaf to witness".to_string())
+    }
+
+    /// Obtains the authentication path for the note commitment at `position`, as of the
+    /// tree state whose root is `root`.
+    ///
+    /// Returns the 32 sibling hashes needed, together with `position`, to reconstruct the
+    /// Merkle path for a spend.
+    fn authentication_path(
+        &self,
+        position: u64,
+        root: [u8; 32],
+    ) -> Result<ffi::AuthPath, String> {
+        let mut root_bytes = &root[..];
+        let as_of_root = H::read(&mut root_bytes)
+            .map_err(|e| format!("Invalid root for authentication path: {}", e))?;
+
+        let path = self
+            .0
+            .authentication_path(Position::from(position), &as_of_root)
+            .ok_or_else(|| {
+                format!(
+                    "No witness for position {} as of the given root",
+                    position,
+                )
+            })?;
+
+        let mut siblings = [[0u8; 32]; MERKLE_DEPTH as usize];
+        for (dst, node) in siblings.iter_mut().zip(path.iter()) {
+            node.write(&mut dst[..]).expect("hash is 32 bytes");
+        }
+        Ok(ffi::AuthPath { position, siblings })
+    }
+}
+
+/// A checkpointed Orchard incremental Merkle tree, capable of producing spend witnesses.
+type OrchardWitnessTree = WitnessTree<MerkleHashOrchard>;
+
+/// Constructs a new empty checkpointed Orchard Merkle tree.
+fn new_orchard_witness_tree() -> Box<OrchardWitnessTree> {
+    Box::new(WitnessTree(WitnessTreeInner::new(100)))
+}
+
+impl OrchardWitnessTree {
+    /// Appends the note commitments in the given bundle to this tree.
+    fn append_bundle(&mut self, bundle: *const OrchardBundle) -> bool {
+        let bundle = unsafe { (bundle as *const orchard::Bundle<Authorized, Amount>).as_ref() };
+
+        if let Some(bundle) = bundle {
+            for action in bundle.actions().iter() {
+                if !self.0.append(&MerkleHashOrchard::from_cmx(action.cmx())) {
+                    error!("Orchard note commitment tree is full.");
+                    return false;
+                }
+            }
+        }
+
+        true
+    }
+}